@@ -0,0 +1,179 @@
+//! A turnkey password-based encryption container, combining `pwhash` key
+//! derivation with `secretbox` authenticated encryption.
+//!
+//! `seal()` encrypts a plaintext under a key derived from a human
+//! password, and bundles everything needed to reverse that (the salt, the
+//! cost parameters, and the nonce) alongside the ciphertext. `open()`
+//! re-derives the key from the password and the sealed bytes, so callers
+//! don't have to track the salt or nonce themselves — but they do need
+//! to supply a `max_opslimit`/`max_memlimit` ceiling, since the cost
+//! parameters are embedded in the (untrusted) sealed blob and must be
+//! capped before `open()` feeds them to `derive_key()`.
+use crypto::pwhash::{self, PwHashError, Salt, OpsLimit, MemLimit, SALTBYTES};
+use crypto::secretbox::{self, Key, Nonce, KEYBYTES, NONCEBYTES};
+
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 1 + SALTBYTES + 8 + 8 + NONCEBYTES;
+
+/// `seal()` encrypts `plaintext` under a key derived from `passwd` using
+/// the given `opslimit`/`memlimit`, and returns a self-describing blob
+/// that `open()` can later decrypt given `passwd` and a cost ceiling at
+/// least as high as `opslimit`/`memlimit`.
+///
+/// It fails with the same `PwHashError` that `pwhash::derive_key()` would,
+/// e.g. `InvalidParams` if `opslimit`/`memlimit` are out of range, or
+/// `OutOfMemory` if `memlimit` is too large for the current machine.
+/// #Example
+/// ```
+/// use sodiumoxide::crypto::pwbox::{seal, open};
+/// use sodiumoxide::crypto::pwhash::{OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
+///
+/// let passwd = "Correct Horse Battery Staple".as_bytes();
+/// let sealed = seal(passwd, b"attack at dawn", OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+/// assert_eq!(open(passwd, &sealed, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap(), b"attack at dawn");
+/// ```
+pub fn seal(passwd: &[u8], plaintext: &[u8],
+            opslimit: OpsLimit, memlimit: MemLimit) -> Result<Vec<u8>, PwHashError> {
+    let OpsLimit(ops) = opslimit;
+    let MemLimit(mem) = memlimit;
+    let salt = pwhash::gen_salt();
+    let nonce = secretbox::gen_nonce();
+
+    let mut kb = [0; KEYBYTES];
+    try!(pwhash::derive_key(&mut kb, passwd, &salt, pwhash::Algorithm::Default,
+                            opslimit, memlimit));
+    let ciphertext = secretbox::seal(plaintext, &nonce, &Key(kb));
+
+    let mut out = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    out.push(VERSION);
+    let Salt(ref sb) = salt;
+    out.extend_from_slice(sb);
+    out.extend_from_slice(&encode_u64(ops as u64));
+    out.extend_from_slice(&encode_u64(mem as u64));
+    let Nonce(ref nb) = nonce;
+    out.extend_from_slice(nb);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// `open()` decrypts a blob produced by `seal()`.
+///
+/// `max_opslimit`/`max_memlimit` cap the cost parameters `open()` is
+/// willing to spend deriving a key: `sealed` carries its own `opslimit`
+/// and `memlimit` so that it remains self-describing, but those values
+/// come from untrusted input and are checked against the caller's
+/// ceiling *before* key derivation runs, so a corrupted or malicious
+/// blob can't force an arbitrarily expensive Argon2 run ahead of
+/// authentication.
+///
+/// It returns `None` if `sealed` is truncated or carries an unsupported
+/// version tag, if its embedded `opslimit`/`memlimit` exceed the given
+/// ceiling, if `passwd` is wrong, or if the ciphertext has been
+/// tampered with.
+pub fn open(passwd: &[u8], sealed: &[u8],
+            max_opslimit: OpsLimit, max_memlimit: MemLimit) -> Option<Vec<u8>> {
+    if sealed.len() < HEADER_LEN || sealed[0] != VERSION {
+        return None;
+    }
+
+    let mut pos = 1;
+    let salt = match Salt::from_slice(&sealed[pos..pos + SALTBYTES]) {
+        Some(salt) => salt,
+        None => return None,
+    };
+    pos += SALTBYTES;
+    let opslimit = OpsLimit(decode_u64(&sealed[pos..pos + 8]) as usize);
+    pos += 8;
+    let memlimit = MemLimit(decode_u64(&sealed[pos..pos + 8]) as usize);
+    pos += 8;
+    let nonce = match Nonce::from_slice(&sealed[pos..pos + NONCEBYTES]) {
+        Some(nonce) => nonce,
+        None => return None,
+    };
+    pos += NONCEBYTES;
+    let ciphertext = &sealed[pos..];
+
+    let OpsLimit(ops) = opslimit;
+    let OpsLimit(max_ops) = max_opslimit;
+    let MemLimit(mem) = memlimit;
+    let MemLimit(max_mem) = max_memlimit;
+    if ops > max_ops || mem > max_mem {
+        return None;
+    }
+
+    let mut kb = [0; KEYBYTES];
+    if pwhash::derive_key(&mut kb, passwd, &salt, pwhash::Algorithm::Default,
+                          opslimit, memlimit).is_err() {
+        return None;
+    }
+    secretbox::open(ciphertext, &nonce, &Key(kb)).ok()
+}
+
+fn encode_u64(n: u64) -> [u8; 8] {
+    let mut b = [0u8; 8];
+    for i in 0..8 {
+        b[i] = ((n >> (8 * i)) & 0xff) as u8;
+    }
+    b
+}
+
+fn decode_u64(b: &[u8]) -> u64 {
+    let mut n = 0u64;
+    for i in 0..8 {
+        n |= (b[i] as u64) << (8 * i);
+    }
+    n
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crypto::pwhash::{OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
+
+    #[test]
+    fn test_seal_open() {
+        let passwd = "Correct Horse Battery Staple".as_bytes();
+        let plaintext = b"attack at dawn";
+        let sealed = seal(passwd, plaintext, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+        assert_eq!(open(passwd, &sealed, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap(),
+                   plaintext);
+    }
+
+    #[test]
+    fn test_open_wrong_password() {
+        let plaintext = b"attack at dawn";
+        let sealed = seal(b"right password", plaintext,
+                          OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+        assert!(open(b"wrong password", &sealed,
+                     OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).is_none());
+    }
+
+    #[test]
+    fn test_open_tampered() {
+        let passwd = "Correct Horse Battery Staple".as_bytes();
+        let sealed = seal(passwd, b"attack at dawn",
+                          OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x20;
+        assert!(open(passwd, &tampered, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).is_none());
+    }
+
+    #[test]
+    fn test_open_truncated() {
+        assert!(open(b"passwd", &[VERSION], OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).is_none());
+    }
+
+    #[test]
+    fn test_open_rejects_cost_above_ceiling() {
+        let passwd = "Correct Horse Battery Staple".as_bytes();
+        let sealed = seal(passwd, b"attack at dawn",
+                          OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+        // A ceiling below what the blob actually asks for must be rejected
+        // without ever running key derivation, so that an attacker-chosen
+        // opslimit/memlimit embedded in a malicious blob can't force an
+        // expensive Argon2 run ahead of authentication.
+        let OpsLimit(ops) = OPSLIMIT_INTERACTIVE;
+        assert!(open(passwd, &sealed, OpsLimit(ops - 1), MEMLIMIT_INTERACTIVE).is_none());
+    }
+}
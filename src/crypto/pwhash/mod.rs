@@ -0,0 +1,585 @@
+//! `crypto_pwhash`, the default password-hashing and key-derivation API
+//!
+//! This is the password-hashing function recommended for new applications.
+//! It currently builds on Argon2, the winner of the Password Hashing
+//! Competition, and lets the caller pick between the Argon2i and Argon2id
+//! variants (or simply use `Algorithm::Default`, which libsodium maps to its
+//! current recommendation).
+//!
+//! Applications that need to stay compatible with password hashes produced
+//! before this module existed can keep using
+//! `scryptsalsa208sha256`.
+use ffi;
+use std::ops::{Index, Range, RangeFrom, RangeFull, RangeTo};
+use randombytes::randombytes_into;
+use libc::{c_int, c_ulonglong, size_t};
+use std::error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub mod scryptsalsa208sha256;
+
+pub const SALTBYTES: usize = ffi::crypto_pwhash_SALTBYTES;
+pub const STRBYTES: usize = ffi::crypto_pwhash_STRBYTES;
+pub const STRPREFIX: &'static str = ffi::crypto_pwhash_STRPREFIX;
+pub const OPSLIMIT_INTERACTIVE: OpsLimit =
+    OpsLimit(ffi::crypto_pwhash_OPSLIMIT_INTERACTIVE);
+pub const MEMLIMIT_INTERACTIVE: MemLimit =
+    MemLimit(ffi::crypto_pwhash_MEMLIMIT_INTERACTIVE);
+pub const OPSLIMIT_MODERATE: OpsLimit =
+    OpsLimit(ffi::crypto_pwhash_OPSLIMIT_MODERATE);
+pub const MEMLIMIT_MODERATE: MemLimit =
+    MemLimit(ffi::crypto_pwhash_MEMLIMIT_MODERATE);
+pub const OPSLIMIT_SENSITIVE: OpsLimit =
+    OpsLimit(ffi::crypto_pwhash_OPSLIMIT_SENSITIVE);
+pub const MEMLIMIT_SENSITIVE: MemLimit =
+    MemLimit(ffi::crypto_pwhash_MEMLIMIT_SENSITIVE);
+
+/// The smallest key length, in bytes, that `derive_key()` will accept.
+pub const BYTES_MIN: usize = ffi::crypto_pwhash_BYTES_MIN;
+/// The largest key length, in bytes, that `derive_key()` will accept.
+pub const BYTES_MAX: usize = ffi::crypto_pwhash_BYTES_MAX;
+/// The shortest password, in bytes, that these functions will accept.
+pub const PASSWD_MIN: usize = ffi::crypto_pwhash_PASSWD_MIN;
+/// The longest password, in bytes, that these functions will accept.
+pub const PASSWD_MAX: usize = ffi::crypto_pwhash_PASSWD_MAX;
+/// The smallest `opslimit` that these functions will accept.
+pub const OPSLIMIT_MIN: usize = ffi::crypto_pwhash_OPSLIMIT_MIN;
+/// The largest `opslimit` that these functions will accept.
+pub const OPSLIMIT_MAX: usize = ffi::crypto_pwhash_OPSLIMIT_MAX;
+/// The smallest `memlimit` that these functions will accept.
+pub const MEMLIMIT_MIN: usize = ffi::crypto_pwhash_MEMLIMIT_MIN;
+/// The largest `memlimit` that these functions will accept.
+pub const MEMLIMIT_MAX: usize = ffi::crypto_pwhash_MEMLIMIT_MAX;
+
+/// `PwHashError` represents the ways in which password hashing, key
+/// derivation or verification can fail.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PwHashError {
+    /// The operating system refused to allocate the amount of memory
+    /// requested by `memlimit`.
+    OutOfMemory,
+    /// A buffer length, `opslimit` or `memlimit` fell outside of the
+    /// range libsodium accepts.
+    InvalidParams,
+    /// The string passed to `pwhash_verify()` is not a password verifier
+    /// this module recognizes.
+    InvalidFormat,
+    /// The password did not match the stored verifier.
+    VerificationFailed,
+}
+
+impl fmt::Display for PwHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            PwHashError::OutOfMemory => "not enough memory to compute the hash",
+            PwHashError::InvalidParams => "key length, opslimit or memlimit out of range",
+            PwHashError::InvalidFormat => "not a recognized password verifier",
+            PwHashError::VerificationFailed => "password does not match",
+        })
+    }
+}
+
+impl error::Error for PwHashError {
+    fn description(&self) -> &str {
+        match *self {
+            PwHashError::OutOfMemory => "not enough memory to compute the hash",
+            PwHashError::InvalidParams => "key length, opslimit or memlimit out of range",
+            PwHashError::InvalidFormat => "not a recognized password verifier",
+            PwHashError::VerificationFailed => "password does not match",
+        }
+    }
+}
+
+/// `OpsLimit` represents the maximum number of computations to perform when
+/// using the functions in this module.
+///
+/// A high `OpsLimit` will make the functions
+/// require more CPU cycles
+#[derive(Copy, Clone)]
+pub struct OpsLimit(pub usize);
+
+/// `MemLimit` represents the maximum amount of RAM that the functions in this
+/// module will use, in bytes.
+///
+/// It is highly recommended to allow the functions to use
+/// at least 16 megabytes.
+#[derive(Copy, Clone)]
+pub struct MemLimit(pub usize);
+
+/// `Algorithm` selects which password-hashing algorithm `derive_key()` uses.
+///
+/// `pwhash()` and `pwhash_verify()` always use `Algorithm::Default` internally,
+/// since libsodium encodes the algorithm that was used inside the returned
+/// `HashedPassword` string and picks it back up automatically on
+/// verification.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    /// The current recommended algorithm. libsodium may change what this
+    /// maps to in future releases.
+    Default,
+    /// Argon2i version 1.3, the side-channel resistant variant.
+    Argon2i13,
+    /// Argon2id version 1.3, a hybrid construction resistant to both
+    /// side-channel and time-memory trade-off attacks.
+    Argon2id13,
+}
+
+impl Algorithm {
+    fn as_c_int(self) -> c_int {
+        match self {
+            Algorithm::Default => ffi::crypto_pwhash_ALG_DEFAULT,
+            Algorithm::Argon2i13 => ffi::crypto_pwhash_ALG_ARGON2I13,
+            Algorithm::Argon2id13 => ffi::crypto_pwhash_ALG_ARGON2ID13,
+        }
+    }
+}
+
+/// `Salt` used for password hashing
+#[derive(Copy)]
+pub struct Salt(pub [u8; SALTBYTES]);
+newtype_clone!(Salt);
+newtype_impl!(Salt, SALTBYTES);
+
+/// `HashedPassword` is a password verifier generated from a password
+///
+/// A `HashedPassword` is zero-terminated, includes only ASCII characters and can
+/// be conveniently stored into SQL databases and other data stores. No
+/// additional information has to be stored in order to verify the password.
+pub struct HashedPassword(pub [u8; STRBYTES]);
+newtype_clone!(HashedPassword);
+newtype_impl!(HashedPassword, STRBYTES);
+
+/// `gen_salt()` randomly generates a new `Salt` for key derivation
+///
+/// THREAD SAFETY: `gen_salt()` is thread-safe provided that you have called
+/// `sodiumoxide::init()` once before using any other function from sodiumoxide.
+pub fn gen_salt() -> Salt {
+    let mut salt = Salt([0; SALTBYTES]);
+    {
+        let Salt(ref mut sb) = salt;
+        randombytes_into(sb);
+    }
+    salt
+}
+
+/// The `derive_key()` function derives a key from a password and a `Salt`
+/// using the given `Algorithm`.
+///
+/// The computed key is stored into out.
+///
+/// `opslimit` represents a maximum amount of computations to perform. Raising
+/// this number will make the function require more CPU cycles to compute a key.
+///
+/// `memlimit` is the maximum amount of RAM that the function will use, in
+/// bytes. It is highly recommended to allow the function to use at least 16
+/// megabytes.
+///
+/// For interactive, online operations, `OPSLIMIT_INTERACTIVE` and
+/// `MEMLIMIT_INTERACTIVE` provide a safe base line for these two
+/// parameters. `OPSLIMIT_MODERATE` and `MEMLIMIT_MODERATE` are recommended
+/// for applications that can tolerate slower logins. For highly sensitive
+/// data, `OPSLIMIT_SENSITIVE` and `MEMLIMIT_SENSITIVE` can be used as an
+/// alternative, at the cost of requiring up to a gigabyte of dedicated RAM
+/// and several seconds on a modern CPU.
+///
+/// The salt should be unpredictable. `gen_salt()` is the easiest way to create a `Salt`.
+///
+/// Keep in mind that in order to produce the same key from the same password,
+/// the same salt, the same algorithm and the same values for opslimit and
+/// memlimit have to be used.
+///
+/// The function returns `Ok(())` on success, `Err(PwHashError::InvalidParams)`
+/// if a buffer length, `opslimit` or `memlimit` is out of range, and
+/// `Err(PwHashError::OutOfMemory)` if the computation didn't complete,
+/// usually because the operating system refused to allocate the amount of
+/// requested memory.
+/// #Example
+/// ```
+/// use sodiumoxide::crypto::secretbox::{Key, KEYBYTES};
+/// use sodiumoxide::crypto::pwhash::{gen_salt, derive_key, Algorithm,
+///                                   OPSLIMIT_INTERACTIVE,
+///                                   MEMLIMIT_INTERACTIVE};
+///
+/// let passwd = "Correct Horse Battery Staple".as_bytes();
+/// let salt = gen_salt();
+/// let mut k = Key([0; KEYBYTES]);
+/// {
+///     let Key(ref mut kb) = k;
+///     derive_key(kb, passwd, &salt,
+///                Algorithm::Default,
+///                OPSLIMIT_INTERACTIVE,
+///                MEMLIMIT_INTERACTIVE).unwrap();
+/// }
+/// ```
+pub fn derive_key(key: &mut [u8], passwd: &[u8], &Salt(ref sb): &Salt,
+                  alg: Algorithm,
+                  OpsLimit(opslimit): OpsLimit,
+                  MemLimit(memlimit): MemLimit) -> Result<(), PwHashError> {
+    if key.len() < BYTES_MIN || key.len() > BYTES_MAX ||
+       passwd.len() < PASSWD_MIN || passwd.len() > PASSWD_MAX ||
+       opslimit < OPSLIMIT_MIN || opslimit > OPSLIMIT_MAX ||
+       memlimit < MEMLIMIT_MIN || memlimit > MEMLIMIT_MAX {
+        return Err(PwHashError::InvalidParams);
+    }
+    if unsafe {
+        ffi::crypto_pwhash(key.as_mut_ptr(),
+                           key.len() as c_ulonglong,
+                           passwd.as_ptr(),
+                           passwd.len() as c_ulonglong,
+                           sb,
+                           opslimit as c_ulonglong,
+                           memlimit as size_t,
+                           alg.as_c_int())
+    } == 0 {
+        Ok(())
+    } else {
+        Err(PwHashError::OutOfMemory)
+    }
+}
+
+/// The `pwhash()` returns a `HashedPassword` which
+/// includes:
+///
+/// - the result of a memory-hard, CPU-intensive hash function applied to the password
+///   `passwd`
+/// - the automatically generated salt used for the
+///   previous computation
+/// - the other parameters required to verify the password: the algorithm,
+///   opslimit and memlimit
+///
+/// `OPSLIMIT_INTERACTIVE` and `MEMLIMIT_INTERACTIVE` are safe baseline
+/// values to use for `opslimit` and `memlimit`.
+///
+/// The function returns `Ok(hashed_password)` on success,
+/// `Err(PwHashError::InvalidParams)` if `passwd.len()`, `opslimit` or
+/// `memlimit` fall outside of `[PASSWD_MIN, PASSWD_MAX]`,
+/// `[OPSLIMIT_MIN, OPSLIMIT_MAX]` or `[MEMLIMIT_MIN, MEMLIMIT_MAX]`
+/// respectively, and `Err(PwHashError::OutOfMemory)` if it didn't
+/// complete successfully.
+/// #Example
+/// ```
+/// use sodiumoxide::crypto::pwhash::{pwhash, HashedPassword,
+///                                   OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
+/// let passwd = "Correct Horse Battery Staple".as_bytes();
+/// let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+/// let pwh_bytes = &pwh[..];
+/// //store pwh_bytes somewhere
+/// ```
+pub fn pwhash(passwd: &[u8], OpsLimit(opslimit): OpsLimit,
+              MemLimit(memlimit): MemLimit) -> Result<HashedPassword, PwHashError> {
+    if passwd.len() < PASSWD_MIN || passwd.len() > PASSWD_MAX ||
+       opslimit < OPSLIMIT_MIN || opslimit > OPSLIMIT_MAX ||
+       memlimit < MEMLIMIT_MIN || memlimit > MEMLIMIT_MAX {
+        return Err(PwHashError::InvalidParams);
+    }
+    let mut out = HashedPassword([0; STRBYTES]);
+    if unsafe {
+        let HashedPassword(ref mut str_) = out;
+        ffi::crypto_pwhash_str(str_,
+                               passwd.as_ptr(),
+                               passwd.len() as c_ulonglong,
+                               opslimit as c_ulonglong,
+                               memlimit as size_t)
+    } == 0 {
+        Ok(out)
+    } else {
+        Err(PwHashError::OutOfMemory)
+    }
+}
+
+/// `pwhash_verify()` verifies that the password `str_` is a valid password
+/// verification string (as generated by `pwhash()`) for `passwd`
+///
+/// It returns `Ok(())` if the verification succeeds,
+/// `Err(PwHashError::InvalidFormat)` if `str_` is not a password verifier
+/// this module recognizes, and `Err(PwHashError::VerificationFailed)` if
+/// `str_` is well-formed but doesn't match `passwd`.
+///
+/// The `InvalidFormat`/`VerificationFailed` split is a best-effort
+/// heuristic: this module only checks `str_` against `STRPREFIX` before
+/// handing it to libsodium, which itself doesn't distinguish "malformed"
+/// from "wrong password" in its return value. A string that starts with
+/// the right prefix but has corrupted fields past it (e.g. a truncated
+/// cost parameter) will still come back as `VerificationFailed`.
+/// #Example
+/// ```
+/// use sodiumoxide::crypto::pwhash::{pwhash, pwhash_verify, HashedPassword,
+///                                   OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
+/// let passwd = "Correct Horse Battery Staple".as_bytes();
+/// // in reality we want to load the password hash from somewhere
+/// // and we might want to create a `HashedPassword` from it using
+/// // `HashedPassword::from_slice(pwhash_bytes).unwrap()`
+/// let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+/// assert!(pwhash_verify(&pwh, passwd).is_ok());
+/// ```
+pub fn pwhash_verify(&HashedPassword(ref str_): &HashedPassword,
+                     passwd: &[u8]) -> Result<(), PwHashError> {
+    let prefix = STRPREFIX.as_bytes();
+    if str_.len() < prefix.len() || &str_[..prefix.len()] != prefix {
+        return Err(PwHashError::InvalidFormat);
+    }
+    if unsafe {
+        ffi::crypto_pwhash_str_verify(str_,
+                                      passwd.as_ptr(),
+                                      passwd.len() as c_ulonglong)
+    } == 0 {
+        Ok(())
+    } else {
+        Err(PwHashError::VerificationFailed)
+    }
+}
+
+/// `pwhash_needs_rehash()` checks whether a `HashedPassword` was produced
+/// with parameters other than the given `opslimit`/`memlimit` (or with a
+/// different algorithm than `Algorithm::Default`).
+///
+/// Applications that raise their cost parameters over time can call this
+/// once a user has logged in successfully to decide whether the stored
+/// hash should be replaced with a freshly computed one using the new,
+/// stronger parameters.
+///
+/// It returns `true` if the string should be regenerated, and `false` if
+/// the parameters already match the given ones; stored parameters that
+/// are stronger than the given ones also return `true`. A `HashedPassword`
+/// that can't be parsed is treated as needing a rehash.
+/// #Example
+/// ```
+/// use sodiumoxide::crypto::pwhash::{pwhash, pwhash_needs_rehash, HashedPassword,
+///                                   OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE,
+///                                   OPSLIMIT_MODERATE, MEMLIMIT_MODERATE};
+/// let passwd = "Correct Horse Battery Staple".as_bytes();
+/// let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+/// assert!(pwhash_needs_rehash(&pwh, OPSLIMIT_MODERATE, MEMLIMIT_MODERATE));
+/// ```
+pub fn pwhash_needs_rehash(&HashedPassword(ref str_): &HashedPassword,
+                          OpsLimit(opslimit): OpsLimit,
+                          MemLimit(memlimit): MemLimit) -> bool {
+    unsafe {
+        ffi::crypto_pwhash_str_needs_rehash(str_,
+                                            opslimit as c_ulonglong,
+                                            memlimit as size_t)
+            != 0
+    }
+}
+
+/// `calibrate()` picks an `OpsLimit` for the given `memlimit` that makes
+/// `derive_key()` take roughly `target` time on the current machine.
+///
+/// Deployments span wildly different hardware, so a fixed preset such as
+/// `OPSLIMIT_INTERACTIVE` can be instant on one machine and sluggish on
+/// another. `calibrate()` instead times real runs of `derive_key()` with a
+/// throwaway password and salt, and binary-searches `opslimit` between
+/// `OPSLIMIT_MIN` and `OPSLIMIT_MAX` until the measured time is close to
+/// `target`.
+///
+/// `memlimit` should be fixed ahead of time to the largest amount of RAM
+/// the deployment can dedicate to password hashing (libsodium recommends
+/// picking a power of two).
+///
+/// # Panics
+/// Panics if a calibration run of `derive_key()` fails, which would
+/// indicate that `memlimit` is too large for the current machine.
+/// #Example
+/// ```
+/// use std::time::Duration;
+/// use sodiumoxide::crypto::pwhash::{calibrate, MEMLIMIT_INTERACTIVE};
+///
+/// let opslimit = calibrate(Duration::from_millis(100), MEMLIMIT_INTERACTIVE);
+/// ```
+pub fn calibrate(target: Duration, MemLimit(memlimit): MemLimit) -> OpsLimit {
+    let passwd = b"sodiumoxide pwhash calibration";
+    let salt = gen_salt();
+    let mut key = [0u8; 32];
+
+    let measure = |opslimit: usize| -> Duration {
+        let started = Instant::now();
+        derive_key(&mut key, passwd, &salt, Algorithm::Default,
+                   OpsLimit(opslimit), MemLimit(memlimit))
+            .expect("calibration run failed; try a smaller memlimit");
+        started.elapsed()
+    };
+
+    let mut low = OPSLIMIT_MIN;
+    let mut high = OPSLIMIT_MAX;
+    let mut opslimit = if memlimit / 32 < OPSLIMIT_MIN {
+        OPSLIMIT_MIN
+    } else if memlimit / 32 > OPSLIMIT_MAX {
+        OPSLIMIT_MAX
+    } else {
+        memlimit / 32
+    };
+
+    loop {
+        let elapsed = measure(opslimit);
+        let tolerance = target / 5;
+        let within_tolerance = if elapsed > target {
+            elapsed - target <= tolerance
+        } else {
+            target - elapsed <= tolerance
+        };
+        if within_tolerance || low >= high {
+            break;
+        }
+        if elapsed < target {
+            low = opslimit + 1;
+        } else {
+            high = opslimit - 1;
+        }
+        if low > high {
+            break;
+        }
+        opslimit = low + (high - low) / 2;
+    }
+    OpsLimit(opslimit)
+}
+
+/// `calibrate_with_memlimit()` is a variant of `calibrate()` that also
+/// picks `memlimit` automatically, instead of requiring the caller to fix
+/// it ahead of time.
+///
+/// It starts from the conservative `MEMLIMIT_INTERACTIVE` preset and
+/// doubles the candidate `memlimit` (libsodium recommends a power of two)
+/// for as long as a `derive_key()` run on the current machine can
+/// actually satisfy it, stopping at `MEMLIMIT_SENSITIVE` — libsodium's
+/// own upper preset. Starting low and growing, rather than probing
+/// downward from `MEMLIMIT_MAX`, avoids ever asking `derive_key()` for
+/// a multi-terabyte allocation: on overcommitting kernels that request
+/// can be accepted as a virtual memory reservation instead of failing
+/// outright, and Argon2 touching that many pages can exhaust real memory
+/// long before the allocation itself is rejected.
+///
+/// # Panics
+/// Panics if even `MEMLIMIT_INTERACTIVE` fails to allocate, which would
+/// indicate the current machine cannot run `derive_key()` at all.
+/// #Example
+/// ```
+/// use std::time::Duration;
+/// use sodiumoxide::crypto::pwhash::calibrate_with_memlimit;
+///
+/// let (opslimit, memlimit) = calibrate_with_memlimit(Duration::from_millis(100));
+/// ```
+pub fn calibrate_with_memlimit(target: Duration) -> (OpsLimit, MemLimit) {
+    let passwd = b"sodiumoxide pwhash calibration";
+    let salt = gen_salt();
+    let mut key = [0u8; 32];
+
+    let can_allocate = |memlimit: usize| {
+        derive_key(&mut key, passwd, &salt, Algorithm::Default,
+                  OpsLimit(OPSLIMIT_MIN), MemLimit(memlimit)).is_ok()
+    };
+
+    let MemLimit(mut memlimit) = MEMLIMIT_INTERACTIVE;
+    if !can_allocate(memlimit) {
+        panic!("calibration failed: not even MEMLIMIT_INTERACTIVE could be allocated");
+    }
+    let MemLimit(ceiling) = MEMLIMIT_SENSITIVE;
+    while memlimit <= ceiling / 2 && can_allocate(memlimit * 2) {
+        memlimit *= 2;
+    }
+
+    let opslimit = calibrate(target, MemLimit(memlimit));
+    (opslimit, MemLimit(memlimit))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pwhash_verify() {
+        use randombytes::randombytes;
+        for i in (0..32usize) {
+            let pw = randombytes(i);
+            let pwh = pwhash(&pw, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+            assert!(pwhash_verify(&pwh, &pw).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_pwhash_memlimit_out_of_range() {
+        let passwd = "Correct Horse Battery Staple".as_bytes();
+        let err = pwhash(passwd, OPSLIMIT_INTERACTIVE, MemLimit(MEMLIMIT_MIN - 1)).unwrap_err();
+        assert_eq!(err, PwHashError::InvalidParams);
+    }
+
+    // A key length read from untrusted input could land outside
+    // [BYTES_MIN, BYTES_MAX]; derive_key() must reject it cleanly instead
+    // of panicking.
+    #[test]
+    fn test_derive_key_length_out_of_range() {
+        let mut kb = [0u8; BYTES_MIN - 1];
+        let salt = gen_salt();
+        let pw = "Correct Horse Battery Staple".as_bytes();
+        let err = derive_key(&mut kb, pw, &salt, Algorithm::Default,
+                             OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap_err();
+        assert_eq!(err, PwHashError::InvalidParams);
+    }
+
+    #[test]
+    fn test_pwhash_verify_tamper() {
+        use randombytes::randombytes;
+        for i in (0..16usize) {
+            let mut pw = randombytes(i);
+            let pwh = pwhash(&pw, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+            for j in (0..pw.len()) {
+                pw[j] ^= 0x20;
+                assert_eq!(pwhash_verify(&pwh, &pw).unwrap_err(), PwHashError::VerificationFailed);
+                pw[j] ^= 0x20;
+            }
+        }
+    }
+
+    #[test]
+    fn test_pwhash_needs_rehash() {
+        let passwd = "Correct Horse Battery Staple".as_bytes();
+        let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
+        assert!(!pwhash_needs_rehash(&pwh, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE));
+        assert!(pwhash_needs_rehash(&pwh, OPSLIMIT_MODERATE, MEMLIMIT_MODERATE));
+    }
+
+    // Re-measures a fresh derive_key() run with the chosen parameters and
+    // checks it landed within a generous band of `target`, so a
+    // regression that breaks binary-search convergence (e.g. a bad
+    // low/high update) doesn't silently pass just because the result
+    // happens to fall within [OPSLIMIT_MIN, OPSLIMIT_MAX] by construction.
+    fn assert_converged(target: Duration, OpsLimit(opslimit): OpsLimit, MemLimit(memlimit): MemLimit) {
+        let passwd = b"sodiumoxide pwhash calibration test";
+        let salt = gen_salt();
+        let mut key = [0u8; 32];
+        let started = Instant::now();
+        derive_key(&mut key, passwd, &salt, Algorithm::Default,
+                  OpsLimit(opslimit), MemLimit(memlimit)).unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed >= target / 5 && elapsed <= target * 5,
+                "derive_key() took {:?}, expected roughly {:?}", elapsed, target);
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let target = Duration::from_millis(50);
+        let OpsLimit(opslimit) = calibrate(target, MEMLIMIT_INTERACTIVE);
+        assert!(opslimit >= OPSLIMIT_MIN && opslimit <= OPSLIMIT_MAX);
+        assert_converged(target, OpsLimit(opslimit), MEMLIMIT_INTERACTIVE);
+    }
+
+    #[test]
+    fn test_calibrate_with_memlimit() {
+        let target = Duration::from_millis(50);
+        let (OpsLimit(opslimit), MemLimit(memlimit)) = calibrate_with_memlimit(target);
+        assert!(opslimit >= OPSLIMIT_MIN && opslimit <= OPSLIMIT_MAX);
+        assert!(memlimit >= MEMLIMIT_MIN && memlimit <= MEMLIMIT_MAX);
+        assert_converged(target, OpsLimit(opslimit), MemLimit(memlimit));
+    }
+
+    #[test]
+    fn test_derive_key_algorithms() {
+        let mut kb = [0u8; 32];
+        let salt = gen_salt();
+        let pw = "Correct Horse Battery Staple".as_bytes();
+        for &alg in [Algorithm::Default, Algorithm::Argon2i13, Algorithm::Argon2id13].iter() {
+            assert!(derive_key(&mut kb, pw, &salt, alg,
+                                OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).is_ok());
+        }
+    }
+}
@@ -1,9 +1,14 @@
 //! `crypto_pwhash_scryptsalsa208sha256`, a particular combination of Scrypt, Salsa20/8
 //! and SHA-256
+//!
+//! New applications should prefer the Argon2-based `pwhash` module, the
+//! current recommended default. This module remains available for
+//! compatibility with password hashes generated before that module existed.
 use ffi;
 use std::ops::{Index, Range, RangeFrom, RangeFull, RangeTo};
 use randombytes::randombytes_into;
 use libc::{c_ulonglong, size_t};
+use super::PwHashError;
 
 pub const SALTBYTES: usize = ffi::crypto_pwhash_scryptsalsa208sha256_SALTBYTES;
 pub const STRBYTES: usize = ffi::crypto_pwhash_scryptsalsa208sha256_STRBYTES;
@@ -17,6 +22,23 @@ pub const OPSLIMIT_SENSITIVE: OpsLimit =
 pub const MEMLIMIT_SENSITIVE: MemLimit =
     MemLimit(ffi::crypto_pwhash_scryptsalsa208sha256_MEMLIMIT_SENSITIVE);
 
+/// The smallest key length, in bytes, that `derive_key()` will accept.
+pub const BYTES_MIN: usize = ffi::crypto_pwhash_scryptsalsa208sha256_BYTES_MIN;
+/// The largest key length, in bytes, that `derive_key()` will accept.
+pub const BYTES_MAX: usize = ffi::crypto_pwhash_scryptsalsa208sha256_BYTES_MAX;
+/// The shortest password, in bytes, that these functions will accept.
+pub const PASSWD_MIN: usize = ffi::crypto_pwhash_scryptsalsa208sha256_PASSWD_MIN;
+/// The longest password, in bytes, that these functions will accept.
+pub const PASSWD_MAX: usize = ffi::crypto_pwhash_scryptsalsa208sha256_PASSWD_MAX;
+/// The smallest `opslimit` that these functions will accept.
+pub const OPSLIMIT_MIN: usize = ffi::crypto_pwhash_scryptsalsa208sha256_OPSLIMIT_MIN;
+/// The largest `opslimit` that these functions will accept.
+pub const OPSLIMIT_MAX: usize = ffi::crypto_pwhash_scryptsalsa208sha256_OPSLIMIT_MAX;
+/// The smallest `memlimit` that these functions will accept.
+pub const MEMLIMIT_MIN: usize = ffi::crypto_pwhash_scryptsalsa208sha256_MEMLIMIT_MIN;
+/// The largest `memlimit` that these functions will accept.
+pub const MEMLIMIT_MAX: usize = ffi::crypto_pwhash_scryptsalsa208sha256_MEMLIMIT_MAX;
+
 /// `OpsLimit` represents the maximum number of computations to perform when
 /// using the functions in this module.
 ///
@@ -87,13 +109,15 @@ pub fn gen_salt() -> Salt {
 /// the same salt, and the same values for opslimit and memlimit have to be
 /// used.
 ///
-/// The function returns `Some(key)` on success and `None` if the computation didn't
-/// complete, usually because the operating system refused to allocate the
-/// amount of requested memory.
+/// The function returns `Ok(())` on success, `Err(PwHashError::InvalidParams)`
+/// if a buffer length, `opslimit` or `memlimit` is out of range, and
+/// `Err(PwHashError::OutOfMemory)` if the computation didn't complete,
+/// usually because the operating system refused to allocate the amount of
+/// requested memory.
 /// #Example
 /// ```
 /// use sodiumoxide::crypto::secretbox::{Key, KEYBYTES};
-/// use sodiumoxide::crypto::pwhash::{gen_salt, derive_key,
+/// use sodiumoxide::crypto::pwhash::scryptsalsa208sha256::{gen_salt, derive_key,
 ///                                   OPSLIMIT_INTERACTIVE,
 ///                                   MEMLIMIT_INTERACTIVE};
 ///
@@ -109,7 +133,13 @@ pub fn gen_salt() -> Salt {
 /// ```
 pub fn derive_key(key: &mut [u8], passwd: &[u8], &Salt(ref sb): &Salt,
                   OpsLimit(opslimit): OpsLimit,
-                  MemLimit(memlimit): MemLimit) -> Option<()> {
+                  MemLimit(memlimit): MemLimit) -> Result<(), PwHashError> {
+    if key.len() < BYTES_MIN || key.len() > BYTES_MAX ||
+       passwd.len() < PASSWD_MIN || passwd.len() > PASSWD_MAX ||
+       opslimit < OPSLIMIT_MIN || opslimit > OPSLIMIT_MAX ||
+       memlimit < MEMLIMIT_MIN || memlimit > MEMLIMIT_MAX {
+        return Err(PwHashError::InvalidParams);
+    }
     if unsafe {
         ffi::crypto_pwhash_scryptsalsa208sha256(key.as_mut_ptr(),
                                                 key.len() as c_ulonglong,
@@ -119,9 +149,9 @@ pub fn derive_key(key: &mut [u8], passwd: &[u8], &Salt(ref sb): &Salt,
                                                 opslimit as c_ulonglong,
                                                 memlimit as size_t)
     } == 0 {
-        Some(())
+        Ok(())
     } else {
-        None
+        Err(PwHashError::OutOfMemory)
     }
 }
 
@@ -137,11 +167,15 @@ pub fn derive_key(key: &mut [u8], passwd: &[u8], &Salt(ref sb): &Salt,
 /// `OPSLIMIT_INTERACTIVE` and `MEMLIMIT_INTERACTIVE` are safe baseline
 /// values to use for `opslimit` and `memlimit`.
 ///
-/// The function returns `Some(hashed_password)` on success and `None` if it didn't complete
-/// successfully
+/// The function returns `Ok(hashed_password)` on success,
+/// `Err(PwHashError::InvalidParams)` if `passwd.len()`, `opslimit` or
+/// `memlimit` fall outside of `[PASSWD_MIN, PASSWD_MAX]`,
+/// `[OPSLIMIT_MIN, OPSLIMIT_MAX]` or `[MEMLIMIT_MIN, MEMLIMIT_MAX]`
+/// respectively, and `Err(PwHashError::OutOfMemory)` if it didn't
+/// complete successfully.
 /// #Example
 /// ```
-/// use sodiumoxide::crypto::pwhash::{pwhash, HashedPassword,
+/// use sodiumoxide::crypto::pwhash::scryptsalsa208sha256::{pwhash, HashedPassword,
 ///                                   OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
 /// let passwd = "Correct Horse Battery Staple".as_bytes();
 /// let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
@@ -149,7 +183,12 @@ pub fn derive_key(key: &mut [u8], passwd: &[u8], &Salt(ref sb): &Salt,
 /// //store pwh_bytes somewhere
 /// ```
 pub fn pwhash(passwd: &[u8], OpsLimit(opslimit): OpsLimit,
-              MemLimit(memlimit): MemLimit) -> Option<HashedPassword> {
+              MemLimit(memlimit): MemLimit) -> Result<HashedPassword, PwHashError> {
+    if passwd.len() < PASSWD_MIN || passwd.len() > PASSWD_MAX ||
+       opslimit < OPSLIMIT_MIN || opslimit > OPSLIMIT_MAX ||
+       memlimit < MEMLIMIT_MIN || memlimit > MEMLIMIT_MAX {
+        return Err(PwHashError::InvalidParams);
+    }
     let mut out = HashedPassword([0; STRBYTES]);
     if unsafe {
         let HashedPassword(ref mut str_) = out;
@@ -159,34 +198,51 @@ pub fn pwhash(passwd: &[u8], OpsLimit(opslimit): OpsLimit,
                                                     opslimit as c_ulonglong,
                                                     memlimit as size_t)
     } == 0 {
-        Some(out)
+        Ok(out)
     } else {
-        None
+        Err(PwHashError::OutOfMemory)
     }
 }
 
 /// `pwhash_verify()` verifies that the password `str_` is a valid password
 /// verification string (as generated by `pwhash()`) for `passwd`
 ///
-/// It returns `true` if the verification succeeds, and `false` on error.
+/// It returns `Ok(())` if the verification succeeds,
+/// `Err(PwHashError::InvalidFormat)` if `str_` is not a password verifier
+/// this module recognizes, and `Err(PwHashError::VerificationFailed)` if
+/// `str_` is well-formed but doesn't match `passwd`.
+///
+/// The `InvalidFormat`/`VerificationFailed` split is a best-effort
+/// heuristic: this module only checks `str_` against `STRPREFIX` before
+/// handing it to libsodium, which itself doesn't distinguish "malformed"
+/// from "wrong password" in its return value. A string that starts with
+/// the right prefix but has corrupted fields past it (e.g. a truncated
+/// cost parameter) will still come back as `VerificationFailed`.
 /// #Example
 /// ```
-/// use sodiumoxide::crypto::pwhash::{pwhash, pwhash_verify, HashedPassword,
+/// use sodiumoxide::crypto::pwhash::scryptsalsa208sha256::{pwhash, pwhash_verify, HashedPassword,
 ///                                   OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE};
 /// let passwd = "Correct Horse Battery Staple".as_bytes();
 /// // in reality we want to load the password hash from somewhere
 /// // and we might want to create a `HashedPassword` from it using
 /// // `HashedPassword::from_slice(pwhash_bytes).unwrap()`
 /// let pwh = pwhash(passwd, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
-/// assert!(pwhash_verify(&pwh, passwd));
+/// assert!(pwhash_verify(&pwh, passwd).is_ok());
 /// ```
 pub fn pwhash_verify(&HashedPassword(ref str_): &HashedPassword,
-                     passwd: &[u8]) -> bool {
-    unsafe {
+                     passwd: &[u8]) -> Result<(), PwHashError> {
+    let prefix = STRPREFIX.as_bytes();
+    if str_.len() < prefix.len() || &str_[..prefix.len()] != prefix {
+        return Err(PwHashError::InvalidFormat);
+    }
+    if unsafe {
         ffi::crypto_pwhash_scryptsalsa208sha256_str_verify(str_,
                                                            passwd.as_ptr(),
                                                            passwd.len() as c_ulonglong)
-            == 0
+    } == 0 {
+        Ok(())
+    } else {
+        Err(PwHashError::VerificationFailed)
     }
 }
 
@@ -205,17 +261,39 @@ mod test {
                            0x3b, 0x9a, 0xe8, 0x3e, 0x05, 0xef, 0xad, 0x25,
                            0xdb, 0x8d, 0x83, 0xb8, 0x3d, 0xb1, 0xde, 0xe3,
                            0x6b, 0xdb, 0xf5, 0x4d, 0xcd, 0x3a, 0x1a, 0x11];
-        derive_key(&mut kb, pw, &salt, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE);
+        derive_key(&mut kb, pw, &salt, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
         assert_eq!(kb, kb_expected);
     }
 
+    #[test]
+    fn test_derive_key_opslimit_out_of_range() {
+        let mut kb = [0u8; 32];
+        let salt = Salt([0; SALTBYTES]);
+        let pw = "Correct Horse Battery Staple".as_bytes();
+        let err = derive_key(&mut kb, pw, &salt, OpsLimit(OPSLIMIT_MIN - 1),
+                             MEMLIMIT_INTERACTIVE).unwrap_err();
+        assert_eq!(err, PwHashError::InvalidParams);
+    }
+
+    // Same bounds-check regression as the Argon2 module's derive_key():
+    // a bogus key length must come back as InvalidParams, not a panic.
+    #[test]
+    fn test_derive_key_length_out_of_range() {
+        let mut kb = [0u8; BYTES_MIN - 1];
+        let salt = Salt([0; SALTBYTES]);
+        let pw = "Correct Horse Battery Staple".as_bytes();
+        let err = derive_key(&mut kb, pw, &salt, OPSLIMIT_INTERACTIVE,
+                             MEMLIMIT_INTERACTIVE).unwrap_err();
+        assert_eq!(err, PwHashError::InvalidParams);
+    }
+
     #[test]
     fn test_pwhash_verify() {
         use randombytes::randombytes;
         for i in (0..32usize) {
             let pw = randombytes(i);
             let pwh = pwhash(&pw, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
-            assert!(pwhash_verify(&pwh, &pw));
+            assert!(pwhash_verify(&pwh, &pw).is_ok());
         }
     }
 
@@ -227,7 +305,7 @@ mod test {
             let pwh = pwhash(&pw, OPSLIMIT_INTERACTIVE, MEMLIMIT_INTERACTIVE).unwrap();
             for j in (0..pw.len()) {
                 pw[j] ^= 0x20;
-                assert!(!pwhash_verify(&pwh, &pw));
+                assert_eq!(pwhash_verify(&pwh, &pw).unwrap_err(), PwHashError::VerificationFailed);
                 pw[j] ^= 0x20;
             }
         }